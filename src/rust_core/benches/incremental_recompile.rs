@@ -0,0 +1,72 @@
+//! Benchmark demonstrating that editing one paragraph of a long document and
+//! recompiling is far cheaper than a cold compile, since `SlotCell`
+//! fingerprinting (see `world.rs`) lets `comemo` reuse the layout work for
+//! everything that didn't change. Run with `cargo bench`; excluded from
+//! `cargo test`.
+
+use std::hint::black_box;
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rust_core::SystemWorld;
+use typst::layout::PagedDocument;
+
+fn long_document() -> String {
+    "= Report\n\n".to_string() + &"Lorem ipsum dolor sit amet. ".repeat(2000)
+}
+
+fn bench_cold_compile(c: &mut Criterion) {
+    let base = long_document();
+
+    c.bench_function("cold compile", |b| {
+        b.iter(|| {
+            let world = SystemWorld::new(
+                PathBuf::from("."),
+                &[],
+                typst::foundations::Dict::default(),
+                &base,
+                false,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+            black_box(typst::compile::<PagedDocument>(&world));
+        });
+    });
+}
+
+fn bench_incremental_recompile(c: &mut Criterion) {
+    let base = long_document();
+    let mut world = SystemWorld::new(
+        PathBuf::from("."),
+        &[],
+        typst::foundations::Dict::default(),
+        &base,
+        false,
+        None,
+        None,
+        None,
+        false,
+    )
+    .unwrap();
+    let _ = typst::compile::<PagedDocument>(&world);
+
+    let mut toggle = false;
+    c.bench_function("incremental recompile after editing one paragraph", |b| {
+        b.iter(|| {
+            toggle = !toggle;
+            let edited = if toggle {
+                base.clone() + "\n\nOne more paragraph edited in."
+            } else {
+                base.clone()
+            };
+            world.set_main_content(&edited);
+            black_box(typst::compile::<PagedDocument>(&world));
+        });
+    });
+}
+
+criterion_group!(benches, bench_cold_compile, bench_incremental_recompile);
+criterion_main!(benches);
@@ -1,6 +1,9 @@
+use std::env;
 use std::fmt::Display;
 
+use typst::diag::{eco_format, StrResult};
 use typst_kit::download::{DownloadState, Downloader, Progress};
+use ureq::{AgentBuilder, Proxy};
 
 pub struct SilentDownload<T>(pub T);
 
@@ -12,8 +15,84 @@ impl<T: Display> Progress for SilentDownload<T> {
     fn print_finish(&mut self, _state: &DownloadState) {}
 }
 
-/// Returns a new downloader.
-pub fn downloader() -> Downloader {
+/// Returns a new downloader for fetching Typst packages.
+///
+/// `proxy` takes precedence when set (HTTP or SOCKS5, anything
+/// [`Proxy::new`] accepts). Otherwise `HTTPS_PROXY` (or `https_proxy`) is
+/// consulted, since the package registry is fetched over HTTPS, unless a
+/// `NO_PROXY` entry matches the registry host, matching the convention
+/// curl and most HTTP clients follow. `HTTP_PROXY` is not read.
+pub fn downloader(proxy: Option<&str>) -> StrResult<Downloader> {
     let user_agent = concat!("typst-net/", env!("CARGO_PKG_VERSION"));
-    Downloader::new(user_agent)
+    let mut builder = AgentBuilder::new().user_agent(user_agent);
+
+    let proxy = match proxy {
+        Some(url) => Some(url.to_string()),
+        None => env_proxy("https"),
+    };
+
+    if let Some(url) = proxy {
+        let proxy = Proxy::new(&url).map_err(|e| eco_format!("invalid proxy url `{url}`: {e}"))?;
+        builder = builder.proxy(proxy);
+    }
+
+    Ok(Downloader::new_with_agent(user_agent, builder.build()))
+}
+
+/// The host the Typst package registry is fetched from, checked against
+/// `NO_PROXY` entries.
+const PACKAGE_HOST: &str = "packages.typst.org";
+
+/// Looks up a proxy URL for `scheme` from the environment, honoring
+/// `NO_PROXY`/`no_proxy` for the Typst package registry host.
+fn env_proxy(scheme: &str) -> Option<String> {
+    let no_proxy = env::var("NO_PROXY").or_else(|_| env::var("no_proxy"));
+    if let Ok(hosts) = no_proxy {
+        if hosts.split(',').any(|entry| no_proxy_matches(PACKAGE_HOST, entry)) {
+            return None;
+        }
+    }
+
+    let var = if scheme.eq_ignore_ascii_case("https") {
+        "HTTPS_PROXY"
+    } else {
+        "HTTP_PROXY"
+    };
+
+    env::var(var).or_else(|_| env::var(var.to_lowercase())).ok()
+}
+
+/// Matches `host` against a single `NO_PROXY` entry, following curl's
+/// conventions: `*` disables proxying entirely, a leading `.` matches any
+/// subdomain, and a bare domain matches itself or any subdomain of it.
+fn no_proxy_matches(host: &str, entry: &str) -> bool {
+    let entry = entry.trim();
+    if entry.is_empty() {
+        return false;
+    }
+    if entry == "*" {
+        return true;
+    }
+
+    let domain = entry.strip_prefix('.').unwrap_or(entry);
+    host == domain || host.ends_with(&format!(".{domain}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_disables_proxying_entirely() {
+        assert!(no_proxy_matches(PACKAGE_HOST, "*"));
+    }
+
+    #[test]
+    fn bare_domain_matches_itself_and_subdomains() {
+        assert!(no_proxy_matches(PACKAGE_HOST, "typst.org"));
+        assert!(no_proxy_matches(PACKAGE_HOST, ".typst.org"));
+        assert!(no_proxy_matches(PACKAGE_HOST, "packages.typst.org"));
+        assert!(!no_proxy_matches(PACKAGE_HOST, "example.com"));
+        assert!(!no_proxy_matches(PACKAGE_HOST, "typst.org.evil.com"));
+    }
 }
@@ -8,11 +8,13 @@ mod download;
 mod query;
 mod world;
 
-use ecow::EcoString;
-use typst::diag::{SourceDiagnostic, StrResult, Warned};
+use typst::diag::{Severity, SourceDiagnostic, StrResult};
 use typst::foundations::Dict;
+use typst::html::{html, HtmlDocument};
 use typst::layout::PagedDocument;
-use world::SystemWorld;
+use typst::syntax::{FileId, Span};
+use typst::World;
+pub use world::SystemWorld;
 
 // This represents the stateful compiler in Rust.
 pub struct Compiler(SystemWorld);
@@ -23,17 +25,34 @@ pub struct Buffer {
     pub len: usize,
 }
 
+/// A single error or warning produced while compiling a document, with the
+/// source span resolved to 1-based line/column so .NET can render squiggles
+/// and jump-to-error. Fields are `-1`/null when the span has no known source
+/// (e.g. a diagnostic raised from outside the document).
 #[repr(C)]
-pub struct Warning {
+pub struct Diagnostic {
+    /// 0 = error, 1 = warning.
+    pub severity: u8,
+    pub path: *mut c_char,
+    pub start_line: i32,
+    pub start_column: i32,
+    pub end_line: i32,
+    pub end_column: i32,
     pub message: *mut c_char,
+    pub hints: *mut *mut c_char,
+    pub hints_len: usize,
+    pub trace: *mut *mut c_char,
+    pub trace_len: usize,
 }
 
 #[repr(C)]
 pub struct CompileResult {
     pub buffers: *mut Buffer,
     pub buffers_len: usize,
-    pub warnings: *mut Warning,
-    pub warnings_len: usize,
+    pub diagnostics: *mut Diagnostic,
+    pub diagnostics_len: usize,
+    /// Rollup message for failures that don't carry their own diagnostics,
+    /// e.g. a fatal `World` error or an export failure.
     pub error: *mut c_char,
 }
 
@@ -42,13 +61,105 @@ impl Default for CompileResult {
         Self {
             buffers: ptr::null_mut(),
             buffers_len: 0,
-            warnings: ptr::null_mut(),
-            warnings_len: 0,
+            diagnostics: ptr::null_mut(),
+            diagnostics_len: 0,
             error: ptr::null_mut(),
         }
     }
 }
 
+/// Builds a `CString` from arbitrary text, stripping embedded NULs first.
+/// Diagnostic messages, hints and traces come straight from Typst source
+/// text and user input, so they can contain `\0`; `CString::new` rejects
+/// that, and this is the one place all of those strings funnel through on
+/// their way across the FFI boundary.
+fn cstring_lossy(s: impl Into<String>) -> CString {
+    let s: String = s.into();
+    CString::new(s).unwrap_or_else(|e| {
+        let mut bytes = e.into_vec();
+        bytes.retain(|&b| b != 0);
+        CString::new(bytes).unwrap_or_default()
+    })
+}
+
+/// Resolves a [`Span`] to its originating file path and 1-based start/end
+/// line/column, looking the `Source` up through `world`.
+fn resolve_span(world: &SystemWorld, span: Span) -> (Option<String>, i32, i32, i32, i32) {
+    let Some(id) = span.id() else {
+        return (None, -1, -1, -1, -1);
+    };
+
+    let path = Some(file_id_path(id));
+
+    let Ok(source) = world.source(id) else {
+        return (path, -1, -1, -1, -1);
+    };
+    let Some(range) = source.range(span) else {
+        return (path, -1, -1, -1, -1);
+    };
+
+    let line_col = |offset: usize| -> (i32, i32) {
+        let line = source.byte_to_line(offset).map(|l| l as i32 + 1).unwrap_or(-1);
+        let column = source.byte_to_column(offset).map(|c| c as i32 + 1).unwrap_or(-1);
+        (line, column)
+    };
+
+    let (start_line, start_column) = line_col(range.start);
+    let (end_line, end_column) = line_col(range.end);
+    (path, start_line, start_column, end_line, end_column)
+}
+
+/// A human-readable path for a `FileId`, including its package spec if any.
+fn file_id_path(id: FileId) -> String {
+    match id.package() {
+        Some(spec) => format!("{spec}/{}", id.vpath().as_rootless_path().display()),
+        None => id.vpath().as_rootless_path().display().to_string(),
+    }
+}
+
+fn to_c_diagnostic(world: &SystemWorld, diag: &SourceDiagnostic) -> Diagnostic {
+    let (path, start_line, start_column, end_line, end_column) = resolve_span(world, diag.span);
+
+    let mut hints: Vec<*mut c_char> = diag
+        .hints
+        .iter()
+        .map(|hint| cstring_lossy(hint.to_string()).into_raw())
+        .collect();
+    hints.shrink_to_fit();
+    let hints_len = hints.len();
+    let hints_ptr = hints.as_mut_ptr();
+    std::mem::forget(hints);
+
+    let mut trace: Vec<*mut c_char> = diag
+        .trace
+        .iter()
+        .map(|point| cstring_lossy(point.v.to_string()).into_raw())
+        .collect();
+    trace.shrink_to_fit();
+    let trace_len = trace.len();
+    let trace_ptr = trace.as_mut_ptr();
+    std::mem::forget(trace);
+
+    Diagnostic {
+        severity: match diag.severity {
+            Severity::Error => 0,
+            Severity::Warning => 1,
+        },
+        path: path.map_or(ptr::null_mut(), |p| cstring_lossy(p).into_raw()),
+        start_line,
+        start_column,
+        end_line,
+        end_column,
+        message: cstring_lossy(diag.message.to_string()).into_raw(),
+        hints: hints_ptr,
+        hints_len,
+        trace: trace_ptr,
+        trace_len,
+    }
+}
+
+/// Creates a compiler, or returns null and writes a message describing why
+/// into `*error` (if non-null) — free it with [`free_string`].
 #[no_mangle]
 pub extern "C" fn create_compiler(
     root: *const c_char,
@@ -57,6 +168,11 @@ pub extern "C" fn create_compiler(
     font_paths_len: usize,
     sys_inputs: *const c_char,
     ignore_system_fonts: bool,
+    proxy: *const c_char,
+    package_cache: *const c_char,
+    package_data: *const c_char,
+    offline: bool,
+    error: *mut *mut c_char,
 ) -> *mut Compiler {
     let root_str = if root.is_null() {
         "."
@@ -81,12 +197,139 @@ pub extern "C" fn create_compiler(
 
     let inputs: Dict = serde_json::from_str(sys_inputs_str).unwrap_or_default();
 
-    match SystemWorld::new(root, &font_paths_vec, inputs, input_str, !ignore_system_fonts) {
+    let proxy_str = if proxy.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(proxy).to_str().ok() }
+    };
+
+    let package_cache = if package_cache.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(package_cache).to_str().ok() }.map(PathBuf::from)
+    };
+    let package_data = if package_data.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(package_data).to_str().ok() }.map(PathBuf::from)
+    };
+
+    match SystemWorld::new(
+        root,
+        &font_paths_vec,
+        inputs,
+        input_str,
+        !ignore_system_fonts,
+        proxy_str,
+        package_cache,
+        package_data,
+        offline,
+    ) {
         Ok(world) => Box::into_raw(Box::new(Compiler(world))),
-        Err(_) => ptr::null_mut(),
+        Err(err) => {
+            if !error.is_null() {
+                unsafe { *error = CString::new(err.to_string()).unwrap_or_default().into_raw() };
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn set_offline(compiler: *mut Compiler, offline: bool) {
+    if compiler.is_null() { return }
+    let compiler = unsafe { &mut *compiler };
+    compiler.0.set_offline(offline);
+}
+
+/// Replaces the proxy. Returns `false` if `compiler` is null or `proxy` is
+/// not valid UTF-8, without writing to `*error`; if the proxy URL is
+/// rejected, returns `false` and writes a message describing why into
+/// `*error` (if non-null) — free it with [`free_string`].
+#[no_mangle]
+pub extern "C" fn set_proxy(
+    compiler: *mut Compiler,
+    proxy: *const c_char,
+    error: *mut *mut c_char,
+) -> bool {
+    if compiler.is_null() { return false }
+    let compiler = unsafe { &mut *compiler };
+
+    let proxy_str = if proxy.is_null() {
+        None
+    } else {
+        match unsafe { CStr::from_ptr(proxy).to_str() } {
+            Ok(s) => Some(s),
+            Err(_) => return false,
+        }
+    };
+
+    match compiler.0.set_proxy(proxy_str) {
+        Ok(()) => true,
+        Err(err) => {
+            if !error.is_null() {
+                unsafe { *error = cstring_lossy(err.to_string()).into_raw() };
+            }
+            false
+        }
     }
 }
 
+#[no_mangle]
+pub extern "C" fn set_main_content(compiler: *mut Compiler, content: *const c_char) -> bool {
+    if compiler.is_null() { return false }
+    let compiler = unsafe { &mut *compiler };
+
+    let content_str = match unsafe { CStr::from_ptr(content).to_str() } {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    compiler.0.set_main_content(content_str);
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn set_source(
+    compiler: *mut Compiler,
+    path: *const c_char,
+    content: *const c_char,
+) -> bool {
+    if compiler.is_null() { return false }
+    let compiler = unsafe { &mut *compiler };
+
+    let path_str = match unsafe { CStr::from_ptr(path).to_str() } {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+    let content_str = match unsafe { CStr::from_ptr(content).to_str() } {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    compiler.0.set_source(path_str, content_str);
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn get_font_faces(compiler: *mut Compiler) -> *mut c_char {
+    if compiler.is_null() { return ptr::null_mut() }
+    let compiler = unsafe { &*compiler };
+
+    let faces = compiler.0.font_faces();
+    let json = serde_json::to_string(&faces).unwrap_or_else(|_| "[]".to_string());
+    CString::new(json).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub extern "C" fn add_font_data(compiler: *mut Compiler, data: *const u8, len: usize) -> bool {
+    if compiler.is_null() || data.is_null() { return false }
+    let compiler = unsafe { &mut *compiler };
+
+    let bytes = unsafe { std::slice::from_raw_parts(data, len) }.to_vec();
+    compiler.0.add_font_data(bytes).is_ok()
+}
+
 #[no_mangle]
 pub extern "C" fn free_compiler(compiler: *mut Compiler) {
     if !compiler.is_null() {
@@ -116,20 +359,56 @@ pub extern "C" fn set_sys_inputs(compiler: *mut Compiler, sys_inputs: *const c_c
     }
 }
 
+/// Converts warnings and (optionally) fatal errors from a compilation into
+/// FFI diagnostics, resolving each span's line/column through `world`.
+fn collect_diagnostics(
+    world: &SystemWorld,
+    warnings: &[SourceDiagnostic],
+    errors: &[SourceDiagnostic],
+) -> Vec<Diagnostic> {
+    warnings
+        .iter()
+        .chain(errors.iter())
+        .map(|d| to_c_diagnostic(world, d))
+        .collect()
+}
+
 fn compile_inner(
     world: &mut SystemWorld,
     format: &str,
     ppi: f32,
-) -> StrResult<(Vec<Vec<u8>>, Vec<SourceDiagnostic>)> {
-    let (document, warnings) = match typst::compile::<PagedDocument>(world) {
-        Warned { output, warnings } => {
-            let doc = output.map_err(|errors| EcoString::from(format!("{:?}", errors)))?;
-            (doc, warnings.to_vec())
-        }
+) -> StrResult<(Vec<Vec<u8>>, Vec<Diagnostic>)> {
+    world.reset();
+
+    if format.eq_ignore_ascii_case("html") {
+        return compile_html(world);
+    }
+
+    let typst::diag::Warned { output, warnings } = typst::compile::<PagedDocument>(world);
+
+    let document = match output {
+        Ok(doc) => doc,
+        Err(errors) => return Ok((Vec::new(), collect_diagnostics(world, &warnings, &errors))),
     };
 
     let buffers = compiler::export(&document, format, ppi, &[])?;
-    Ok((buffers, warnings))
+    Ok((buffers, collect_diagnostics(world, &warnings, &[])))
+}
+
+/// Compiles to an [`HtmlDocument`] instead of a [`PagedDocument`] and emits
+/// its rendered markup as a single buffer.
+fn compile_html(world: &mut SystemWorld) -> StrResult<(Vec<Vec<u8>>, Vec<Diagnostic>)> {
+    let typst::diag::Warned { output, warnings } = typst::compile::<HtmlDocument>(world);
+
+    let document = match output {
+        Ok(doc) => doc,
+        Err(errors) => return Ok((Vec::new(), collect_diagnostics(world, &warnings, &errors))),
+    };
+
+    match html(&document) {
+        Ok(markup) => Ok((vec![markup.into_bytes()], collect_diagnostics(world, &warnings, &[]))),
+        Err(errors) => Ok((Vec::new(), collect_diagnostics(world, &warnings, &errors))),
+    }
 }
 
 #[no_mangle]
@@ -142,7 +421,7 @@ pub extern "C" fn compile(
     let format_str = unsafe { CStr::from_ptr(format).to_str().unwrap_or("pdf") };
 
     match compile_inner(&mut compiler.0, format_str, ppi) {
-        Ok((buffers, warnings)) => {
+        Ok((buffers, mut diagnostics)) => {
             let mut c_buffers: Vec<Buffer> = buffers.into_iter().map(|mut b| {
                 b.shrink_to_fit();
                 let buffer = Buffer { ptr: b.as_mut_ptr(), len: b.len() };
@@ -150,29 +429,24 @@ pub extern "C" fn compile(
                 buffer
             }).collect();
 
-            let mut c_warnings: Vec<Warning> = warnings.into_iter().map(|w| {
-                let message = CString::new(w.message.to_string()).unwrap().into_raw();
-                Warning { message }
-            }).collect();
-
             c_buffers.shrink_to_fit();
-            c_warnings.shrink_to_fit();
+            diagnostics.shrink_to_fit();
 
             let result = CompileResult {
                 buffers: c_buffers.as_mut_ptr(),
                 buffers_len: c_buffers.len(),
-                warnings: c_warnings.as_mut_ptr(),
-                warnings_len: c_warnings.len(),
+                diagnostics: diagnostics.as_mut_ptr(),
+                diagnostics_len: diagnostics.len(),
                 error: ptr::null_mut(),
             };
 
             std::mem::forget(c_buffers);
-            std::mem::forget(c_warnings);
+            std::mem::forget(diagnostics);
 
             result
         }
         Err(err) => {
-            let error_str = CString::new(err.to_string()).unwrap();
+            let error_str = cstring_lossy(err.to_string());
             CompileResult { error: error_str.into_raw(), ..Default::default() }
         }
     }
@@ -187,10 +461,27 @@ pub extern "C" fn free_compile_result(result: CompileResult) {
                 let _ = Vec::from_raw_parts(buffer.ptr, buffer.len, buffer.len);
             }
         }
-        if !result.warnings.is_null() {
-            let warnings = Vec::from_raw_parts(result.warnings, result.warnings_len, result.warnings_len);
-            for warning in warnings {
-                let _ = CString::from_raw(warning.message);
+        if !result.diagnostics.is_null() {
+            let diagnostics = Vec::from_raw_parts(
+                result.diagnostics,
+                result.diagnostics_len,
+                result.diagnostics_len,
+            );
+            for diagnostic in diagnostics {
+                if !diagnostic.path.is_null() {
+                    let _ = CString::from_raw(diagnostic.path);
+                }
+                let _ = CString::from_raw(diagnostic.message);
+
+                let hints = Vec::from_raw_parts(diagnostic.hints, diagnostic.hints_len, diagnostic.hints_len);
+                for hint in hints {
+                    let _ = CString::from_raw(hint);
+                }
+
+                let trace = Vec::from_raw_parts(diagnostic.trace, diagnostic.trace_len, diagnostic.trace_len);
+                for point in trace {
+                    let _ = CString::from_raw(point);
+                }
             }
         }
         if !result.error.is_null() {
@@ -222,8 +513,8 @@ pub extern "C" fn query(
     };
 
     match query::query(&mut compiler.0, &command) {
-        Ok(result) => CString::new(result).unwrap().into_raw(),
-        Err(err) => CString::new(format!("Error during query: {}", err)).unwrap().into_raw(),
+        Ok(result) => cstring_lossy(result).into_raw(),
+        Err(err) => cstring_lossy(format!("Error during query: {}", err)).into_raw(),
     }
 }
 
@@ -234,3 +525,26 @@ pub extern "C" fn free_string(s: *mut c_char) {
         let _ = CString::from_raw(s);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use typst::syntax::package::{PackageSpec, PackageVersion};
+    use typst::syntax::VirtualPath;
+
+    use super::*;
+
+    /// Regression test for a missing separator between the package spec and
+    /// the file's vpath, which glued them into e.g.
+    /// `@preview/pkg:1.0.0main.typ` and broke .NET's jump-to-error.
+    #[test]
+    fn file_id_path_separates_package_spec_from_vpath() {
+        let spec = PackageSpec {
+            namespace: "preview".into(),
+            name: "pkg".into(),
+            version: PackageVersion { major: 1, minor: 0, patch: 0 },
+        };
+        let id = FileId::new(Some(spec), VirtualPath::new("main.typ"));
+
+        assert_eq!(file_id_path(id), "@preview/pkg:1.0.0/main.typ");
+    }
+}
@@ -4,7 +4,8 @@ use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, OnceLock};
 
 use chrono::{DateTime, Datelike, Local};
-use typst::diag::{FileError, FileResult, StrResult};
+use serde::Serialize;
+use typst::diag::{eco_format, FileError, FileResult, PackageError, StrResult};
 use typst::foundations::{Bytes, Datetime};
 use typst::syntax::{FileId, Source, VirtualPath};
 use typst::text::{Font, FontBook};
@@ -15,7 +16,7 @@ use typst_kit::{
     package::PackageStorage,
 };
 
-use crate::download::SlientDownload;
+use crate::download::SilentDownload;
 
 
 /// A world that provides access to the operating system.
@@ -30,15 +31,46 @@ pub struct SystemWorld {
     book: LazyHash<FontBook>,
     /// Locations of and storage for lazily loaded fonts.
     fonts: Arc<typst_kit::fonts::Fonts>,
+    /// Fonts registered directly from memory buffers, appended after
+    /// `fonts.fonts` in the index space `World::font` resolves against.
+    memory_fonts: Vec<Font>,
     /// Maps file ids to source files and buffers.
     slots: Mutex<HashMap<FileId, FileSlot>>,
     /// Holds information about where packages are stored.
     package_storage: PackageStorage,
+    /// The cache/data directories `package_storage` was built with, kept
+    /// around so `set_proxy` can rebuild it without reverting to the OS
+    /// default directories.
+    package_cache: Option<PathBuf>,
+    package_data: Option<PathBuf>,
+    /// The directory `package_storage` caches downloaded packages in, kept
+    /// alongside it so offline mode can check the cache without touching
+    /// the network.
+    package_cache_dir: PathBuf,
+    /// When `true`, `preview` packages are resolved from `package_cache_dir`
+    /// only; a missing one is a `FileError` instead of a download attempt.
+    /// Other namespaces resolve as they do online.
+    offline: bool,
     /// The current datetime if requested. This is stored here to ensure it is
     /// always the same within one compilation. Reset between compilations.
     now: OnceLock<DateTime<Local>>,
 }
 
+/// One font face discovered or registered in a [`SystemWorld`], mirroring a
+/// fontconfig-style query so callers can pre-validate that a document's
+/// requested families resolve.
+#[derive(Serialize)]
+pub struct FontFace {
+    pub family: String,
+    pub style: String,
+    pub weight: u16,
+    pub stretch: f64,
+    /// `true` if the face has no backing file — Typst's bundled fallback
+    /// fonts and faces registered via [`SystemWorld::add_font_data`].
+    /// `false` if it was found on disk (`font_paths` or the system).
+    pub embedded: bool,
+}
+
 impl World for SystemWorld {
     fn library(&self) -> &LazyHash<Library> {
         &self.library
@@ -53,15 +85,22 @@ impl World for SystemWorld {
     }
 
     fn source(&self, id: FileId) -> FileResult<Source> {
-        self.slot(id, |slot| slot.source(&self.root, &self.package_storage))
+        self.slot(id, |slot| {
+            slot.source(&self.root, &self.package_storage, &self.package_cache_dir, self.offline)
+        })
     }
 
     fn file(&self, id: FileId) -> FileResult<Bytes> {
-        self.slot(id, |slot| slot.file(&self.root, &self.package_storage))
+        self.slot(id, |slot| {
+            slot.file(&self.root, &self.package_storage, &self.package_cache_dir, self.offline)
+        })
     }
 
     fn font(&self, index: usize) -> Option<Font> {
-        self.fonts.fonts[index].get()
+        match self.fonts.fonts.get(index) {
+            Some(slot) => slot.get(),
+            None => self.memory_fonts.get(index - self.fonts.fonts.len()).cloned(),
+        }
     }
 
     fn today(&self, offset: Option<i64>) -> Option<Datetime> {
@@ -87,6 +126,10 @@ impl SystemWorld {
         inputs: typst::foundations::Dict,
         main_content: &str,
         include_system_fonts: bool,
+        proxy: Option<&str>,
+        package_cache: Option<PathBuf>,
+        package_data: Option<PathBuf>,
+        offline: bool,
     ) -> StrResult<Self> {
         let mut font_searcher = FontSearcher::new();
         font_searcher.include_system_fonts(include_system_fonts);
@@ -98,6 +141,8 @@ impl SystemWorld {
         main_slot.source.init(Source::new(main_id, main_content.to_string()));
         slots.insert(main_id, main_slot);
 
+        let package_cache_dir = package_cache.clone().unwrap_or_else(default_package_cache_dir);
+
         Ok(Self {
             root,
             main: main_id,
@@ -109,12 +154,112 @@ impl SystemWorld {
             ),
             book: LazyHash::new(fonts.book.clone()),
             fonts: Arc::new(fonts),
+            memory_fonts: Vec::new(),
             slots: Mutex::new(slots),
-            package_storage: PackageStorage::new(None, None, crate::download::downloader()),
+            package_storage: PackageStorage::new(
+                package_cache.clone(),
+                package_data.clone(),
+                crate::download::downloader(proxy)?,
+            ),
+            package_cache,
+            package_data,
+            package_cache_dir,
+            offline,
             now: OnceLock::new(),
         })
     }
 
+    /// Replaces the proxy used to fetch Typst packages, rebuilding the
+    /// downloader that backs [`PackageStorage`]. Pass `None` to fall back to
+    /// environment-variable detection. Reuses the cache/data directories the
+    /// compiler was created with.
+    pub fn set_proxy(&mut self, proxy: Option<&str>) -> StrResult<()> {
+        self.package_storage = PackageStorage::new(
+            self.package_cache.clone(),
+            self.package_data.clone(),
+            crate::download::downloader(proxy)?,
+        );
+        Ok(())
+    }
+
+    /// Switches between normal and strict offline package resolution. In
+    /// offline mode, a package missing from the local cache is a
+    /// `FileError` instead of a download attempt.
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Lists every font face this world can currently resolve.
+    pub fn font_faces(&self) -> Vec<FontFace> {
+        self.book
+            .iter()
+            .enumerate()
+            .map(|(index, info)| FontFace {
+                family: info.family.clone(),
+                style: format!("{:?}", info.variant.style).to_lowercase(),
+                weight: info.variant.weight.to_number(),
+                stretch: info.variant.stretch.to_ratio().get(),
+                embedded: self
+                    .fonts
+                    .fonts
+                    .get(index)
+                    .map_or(true, |slot| slot.path().is_none()),
+            })
+            .collect()
+    }
+
+    /// Registers every font face found in `data`, a complete font file or
+    /// collection, so it resolves like any other font without needing a
+    /// filesystem font directory. Returns the number of faces added.
+    pub fn add_font_data(&mut self, data: Vec<u8>) -> StrResult<usize> {
+        let bytes = Bytes::new(data);
+        let mut book = (*self.book).clone();
+        let mut added = 0u32;
+
+        while let Some(font) = Font::new(bytes.clone(), added) {
+            book.insert(font.info().clone());
+            self.memory_fonts.push(font);
+            added += 1;
+        }
+
+        if added == 0 {
+            return Err(eco_format!("no valid font faces found in the supplied data"));
+        }
+
+        self.book = LazyHash::new(book);
+        Ok(added as usize)
+    }
+
+    /// Replaces the content of the `<main>` source, in place, so repeated
+    /// edits reuse the `SlotCell`/`comemo` memoization instead of forcing a
+    /// cold recompile.
+    pub fn set_main_content(&mut self, content: &str) {
+        let main = self.main;
+        self.write_source(main, content);
+    }
+
+    /// Adds or overwrites an in-memory auxiliary source at `path`, relative
+    /// to the project root, without touching the filesystem.
+    pub fn set_source(&mut self, path: &str, content: &str) {
+        let id = FileId::new(None, VirtualPath::new(path));
+        self.write_source(id, content);
+    }
+
+    fn write_source(&mut self, id: FileId, content: &str) {
+        let mut slots = self.slots.lock().unwrap();
+        let slot = slots.entry(id).or_insert_with(|| FileSlot::new(id));
+        match &mut slot.source.data {
+            Some(Ok(source)) => source.replace(content),
+            _ => slot.source.init(Source::new(id, content.to_string())),
+        }
+    }
+
+    /// Clears cached state that must not outlive a single compilation, such
+    /// as the `today()` timestamp, so it's fresh again on the next call.
+    pub fn reset(&mut self) {
+        self.now.take();
+    }
+
     fn slot<F, T>(&self, id: FileId, f: F) -> T
     where
         F: FnOnce(&mut FileSlot) -> T,
@@ -143,10 +288,12 @@ impl FileSlot {
         &mut self,
         project_root: &Path,
         package_storage: &PackageStorage,
+        package_cache_dir: &Path,
+        offline: bool,
     ) -> FileResult<Source> {
         let id = self.id;
         self.source.get_or_init(
-            || system_path(project_root, id, package_storage),
+            || system_path(project_root, id, package_storage, package_cache_dir, offline),
             |data, prev| {
                 let text = decode_utf8(&data)?;
                 if let Some(mut prev) = prev {
@@ -159,25 +306,61 @@ impl FileSlot {
         )
     }
 
-    fn file(&mut self, project_root: &Path, package_storage: &PackageStorage) -> FileResult<Bytes> {
+    fn file(
+        &mut self,
+        project_root: &Path,
+        package_storage: &PackageStorage,
+        package_cache_dir: &Path,
+        offline: bool,
+    ) -> FileResult<Bytes> {
         let id = self.id;
         self.file.get_or_init(
-            || system_path(project_root, id, package_storage),
+            || system_path(project_root, id, package_storage, package_cache_dir, offline),
             |data, _| Ok(Bytes::new(data)),
         )
     }
 }
 
-fn system_path(root: &Path, id: FileId, package_storage: &PackageStorage) -> FileResult<PathBuf> {
+fn system_path(
+    root: &Path,
+    id: FileId,
+    package_storage: &PackageStorage,
+    package_cache_dir: &Path,
+    offline: bool,
+) -> FileResult<PathBuf> {
     let buf;
     let mut root = root;
     if let Some(spec) = id.package() {
-        buf = package_storage.prepare_package(spec, &mut SlientDownload(&spec))?;
+        // Only `preview` packages are ever fetched over the network; other
+        // namespaces (e.g. `local`) already resolve from the package data
+        // directory without touching it, so let `prepare_package` keep
+        // handling those even in offline mode.
+        buf = if offline && spec.namespace.as_str() == "preview" {
+            let dir = package_cache_dir
+                .join(spec.namespace.as_str())
+                .join(spec.name.as_str())
+                .join(spec.version.to_string());
+            if !dir.exists() {
+                return Err(FileError::Package(PackageError::NotFound(spec.clone())));
+            }
+            dir
+        } else {
+            package_storage.prepare_package(spec, &mut SilentDownload(&spec))?
+        };
         root = &buf;
     }
     id.vpath().resolve(root).ok_or(FileError::AccessDenied)
 }
 
+/// The cache directory `PackageStorage` falls back to when no explicit
+/// package-cache directory is given.
+fn default_package_cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("typst")
+        .join("packages")
+}
+
 struct SlotCell<T> {
     data: Option<FileResult<T>>,
     fingerprint: u128,
@@ -240,3 +423,95 @@ fn decode_utf8(buf: &[u8]) -> FileResult<&str> {
         buf.strip_prefix(b"\xef\xbb\xbf").unwrap_or(buf),
     )?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_world(main_content: &str) -> SystemWorld {
+        SystemWorld::new(
+            PathBuf::from("."),
+            &[],
+            typst::foundations::Dict::default(),
+            main_content,
+            false,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+    }
+
+    /// `set_main_content` must update the `<main>` slot in place (via
+    /// `Source::replace`) rather than swap in a fresh `Source`, since that's
+    /// what lets `comemo` keep memoizing everything the edit didn't touch
+    /// instead of forcing a full relayout on every keystroke.
+    #[test]
+    fn set_main_content_replaces_the_source_in_place() {
+        let mut world = test_world("= Report\n\nFirst paragraph.");
+        let main = world.main();
+
+        assert_eq!(world.source(main).unwrap().text(), "= Report\n\nFirst paragraph.");
+
+        world.set_main_content("= Report\n\nEdited paragraph.");
+
+        assert_eq!(
+            world.slots.lock().unwrap().len(),
+            1,
+            "editing main content should reuse its existing slot, not add a new one"
+        );
+        assert_eq!(world.source(main).unwrap().text(), "= Report\n\nEdited paragraph.");
+    }
+
+    /// `set_source` should likewise update an already-registered auxiliary
+    /// path in place rather than duplicate its slot.
+    #[test]
+    fn set_source_overwrites_an_existing_auxiliary_in_place() {
+        let mut world = test_world("= Report");
+        world.set_source("chapter.typ", "First chapter.");
+        world.set_source("chapter.typ", "Second chapter.");
+
+        assert_eq!(world.slots.lock().unwrap().len(), 2);
+
+        let id = FileId::new(None, VirtualPath::new("chapter.typ"));
+        assert_eq!(world.source(id).unwrap().text(), "Second chapter.");
+    }
+
+    /// In offline mode, a `preview` package missing from the cache must be a
+    /// `FileError`, not a download attempt — this is the strict short-circuit
+    /// `system_path` applies to the `preview` namespace only.
+    #[test]
+    fn offline_mode_reports_missing_preview_package_instead_of_downloading() {
+        use typst::syntax::package::{PackageSpec, PackageVersion};
+
+        let mut world = test_world("= Report");
+        world.set_offline(true);
+
+        let spec = PackageSpec {
+            namespace: "preview".into(),
+            name: "does-not-exist".into(),
+            version: PackageVersion { major: 0, minor: 0, patch: 1 },
+        };
+        let id = FileId::new(Some(spec), VirtualPath::new("lib.typ"));
+
+        assert!(matches!(
+            world.file(id),
+            Err(FileError::Package(PackageError::NotFound(_)))
+        ));
+    }
+
+    /// With no `font_paths` and system fonts excluded, every discovered face
+    /// comes from Typst's bundled fallback set and must be reported as
+    /// embedded — regression test for a prior version of `font_faces` that
+    /// conflated "registered via `add_font_data`" with "has no backing file"
+    /// and marked these as `embedded: false`.
+    #[test]
+    fn bundled_fallback_fonts_are_reported_as_embedded() {
+        let world = test_world("= Report");
+        let faces = world.font_faces();
+
+        assert!(!faces.is_empty());
+        assert!(faces.iter().all(|face| face.embedded));
+    }
+}